@@ -1,10 +1,15 @@
-use graphics;
 use graphics::{clear, rectangle};
 use piston::window::WindowSettings;
-use piston_window::{PistonWindow, Transformed, UpdateEvent, Window, AdvancedWindow, Key, Button, PressEvent};
+use piston_window::{
+    PistonWindow, Transformed, UpdateEvent, Window, AdvancedWindow, Key, Button, MouseButton,
+    PressEvent, ReleaseEvent, MouseCursorEvent,
+};
 use rand::prelude::*;
 
-use rand::thread_rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::slice::IterMut;
 
 const CELL_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
@@ -23,34 +28,255 @@ const SEED_SPAWN_RATE: f64 = 1.0 / 4.2;
 
 const CELL_TICK_RATE: f64 = 60.0; // Tick rate in Hz
 
-type CellGrid = [bool; CELL_WIDTH * CELL_HEIGHT];
+const SAVE_FILE: &str = "world.json";
+
+/// A cell's state: `0` is dead, `1` is alive, and anything higher is a
+/// type-specific refractory/decay state (see `CellData`).
+type CellId = u16;
+
+type CellGrid = [CellId; CELL_WIDTH * CELL_HEIGHT];
+
+/// Render data for one state id, looked up by `draw_2d` for every non-zero
+/// cell. Reuses the engine's `[f32; 4]` RGBA color format.
+#[derive(Clone, Copy)]
+struct CellData {
+    pub color: [f32; 4],
+}
 
 #[derive(Clone)]
 struct CellState {
-    pub x: usize,
-    pub y: usize,
-    pub alive: bool,
-    pub neighbors_alive: u8,
+    pub state: CellId,
+    /// Count of this cell's 8 neighbors whose state is exactly `1` (the
+    /// "firing"/live state) — not a genuine per-type count keyed off the
+    /// cell's own `state`. Deliberate scope reduction: every rule shipped so
+    /// far (life-like B/S, Brian's Brain) only ever needs to know how many
+    /// neighbors are firing, never how many share a cell's own type. A
+    /// future automaton with two independently-live types would need this
+    /// field generalized to count neighbors matching `state` instead.
+    pub firing_neighbors: u8,
 }
 
-type CellRule = Box<dyn FnMut(&CellGrid, CellState) -> bool>;
+type CellRule = Box<dyn FnMut(&CellGrid, CellState) -> CellId>;
 
 struct CellRules {
     rules: Vec<CellRule>,
 }
 
-impl CellRules where  {
+impl CellRules {
     pub fn new() -> Self {
         CellRules { rules: vec![] }
     }
-    pub fn add_rule<F: 'static>(&mut self, f: F) where F: FnMut(&CellGrid, CellState) -> bool {
+    pub fn add_rule<F: FnMut(&CellGrid, CellState) -> CellId + 'static>(&mut self, f: F) {
         self.rules.push(Box::new(f));
     }
     pub fn iter_rules(&mut self) -> IterMut<'_, CellRule> {
         self.rules.iter_mut()
     }
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+}
+
+/// A standard Life "B/S" rulestring (e.g. `"B3/S23"`), parsed into a birth
+/// set and a survival set of neighbor counts in `0..=8`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct RuleString {
+    pub birth: HashSet<u8>,
+    pub survival: HashSet<u8>,
+}
+
+impl RuleString {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let mut birth = HashSet::new();
+        let mut survival = HashSet::new();
+        for part in s.split('/') {
+            let mut chars = part.chars();
+            let set = match chars.next() {
+                Some('B') | Some('b') => &mut birth,
+                Some('S') | Some('s') => &mut survival,
+                Some(other) => return Err(format!("rulestring segment must start with B or S, got '{}'", other)),
+                None => return Err("rulestring has an empty segment".to_string()),
+            };
+            for c in chars {
+                let digit = c.to_digit(10).ok_or_else(|| format!("'{}' is not a digit 0-8", c))?;
+                if digit > 8 {
+                    return Err(format!("neighbor count {} is out of range 0-8", digit));
+                }
+                set.insert(digit as u8);
+            }
+        }
+        Ok(RuleString { birth, survival })
+    }
+
+    /// Builds the `CellRule` this rulestring describes: alive (state `1`)
+    /// cells survive when their same-type neighbor count is in `survival`,
+    /// dead (state `0`) cells are born when it's in `birth`; any other state
+    /// (e.g. a decaying cell from a different rule) passes through
+    /// untouched, since B/S rulestrings only ever describe two states.
+    pub fn to_rule(&self) -> CellRule {
+        let birth = self.birth.clone();
+        let survival = self.survival.clone();
+        Box::new(move |_grid: &CellGrid, cell: CellState| {
+            if cell.state == 1 {
+                if survival.contains(&cell.firing_neighbors) { 1 } else { 0 }
+            } else if cell.state == 0 {
+                if birth.contains(&cell.firing_neighbors) { 1 } else { 0 }
+            } else {
+                cell.state
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod rule_string_tests {
+    use super::*;
+
+    fn set(digits: &[u8]) -> HashSet<u8> {
+        digits.iter().copied().collect()
+    }
+
+    #[test]
+    fn parse_rejects_segment_not_starting_with_b_or_s() {
+        let err = RuleString::parse("X3/S23").unwrap_err();
+        assert!(err.contains("must start with B or S"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn parse_rejects_empty_segment() {
+        let err = RuleString::parse("B3/").unwrap_err();
+        assert!(err.contains("empty segment"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn parse_rejects_non_digit_character() {
+        let err = RuleString::parse("B3x/S23").unwrap_err();
+        assert!(err.contains("not a digit"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn parse_rejects_digit_out_of_range() {
+        let err = RuleString::parse("B9/S23").unwrap_err();
+        assert!(err.contains("out of range"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn parse_round_trips_conways_life() {
+        let parsed = RuleString::parse("B3/S23").unwrap();
+        assert_eq!(parsed, RuleString { birth: set(&[3]), survival: set(&[2, 3]) });
+    }
+
+    #[test]
+    fn parse_round_trips_highlife() {
+        let parsed = RuleString::parse("B36/S23").unwrap();
+        assert_eq!(parsed, RuleString { birth: set(&[3, 6]), survival: set(&[2, 3]) });
+    }
+
+    #[test]
+    fn parse_round_trips_seeds_with_empty_survival() {
+        let parsed = RuleString::parse("B2/S").unwrap();
+        assert_eq!(parsed, RuleString { birth: set(&[2]), survival: set(&[]) });
+    }
+
+    #[test]
+    fn rule_presets_labels_round_trip_through_save_load_matching() {
+        for preset in RULE_PRESETS.iter() {
+            let label = preset.label();
+            let found = RULE_PRESETS.iter().position(|p| p.label() == label);
+            assert!(found.is_some(), "label {} did not round-trip through preset matching", label);
+        }
+    }
+
+    #[test]
+    fn brians_brain_label_round_trips_through_save_load_matching() {
+        let label = RulePreset::BriansBrain(2).label();
+        assert_eq!(label, "BriansBrain(2)");
+        let idx = RULE_PRESETS.iter().position(|p| p.label() == label);
+        assert_eq!(idx, Some(3));
+    }
+}
+
+/// Color table for a two-state (dead/alive) rule like any life-like B/S
+/// rulestring; index `0` is never drawn, index `1` is the live cell color.
+fn life_like_colors() -> Vec<CellData> {
+    vec![CellData { color: BG_COLOR }, CellData { color: CELL_COLOR }]
+}
+
+/// A "Generations"-style rule: dead cells (state `0`) are born on exactly 2
+/// live neighbors, a firing cell (state `1`) always moves into its first
+/// refractory state, and refractory states `2..=refractory_states + 1`
+/// always advance to the next one regardless of neighbors until the cell
+/// dies. Only state `1` counts toward a neighbor's `firing_neighbors`, so
+/// refractory cells don't prop up new births the way Brian's Brain expects.
+fn brians_brain_rule(refractory_states: u16) -> CellRule {
+    Box::new(move |_grid: &CellGrid, cell: CellState| {
+        if cell.state == 0 {
+            if cell.firing_neighbors == 2 { 1 } else { 0 }
+        } else if cell.state < 1 + refractory_states {
+            cell.state + 1
+        } else {
+            0
+        }
+    })
+}
+
+/// Color table for `brians_brain_rule`: state `1` is the firing color,
+/// refractory states fade linearly from it toward the background.
+fn brians_brain_colors(refractory_states: u16) -> Vec<CellData> {
+    let mut table = life_like_colors();
+    for i in 0..refractory_states {
+        let t = (i + 1) as f32 / (refractory_states + 1) as f32;
+        table.push(CellData {
+            color: [
+                CELL_COLOR[0] + (BG_COLOR[0] - CELL_COLOR[0]) * t,
+                CELL_COLOR[1] + (BG_COLOR[1] - CELL_COLOR[1]) * t,
+                CELL_COLOR[2] + (BG_COLOR[2] - CELL_COLOR[2]) * t,
+                1.0,
+            ],
+        });
+    }
+    table
+}
+
+/// A rule + its render palette, selectable at runtime.
+#[derive(Clone, Copy)]
+enum RulePreset {
+    LifeLike(&'static str),
+    BriansBrain(u16),
+}
+
+impl RulePreset {
+    pub fn label(&self) -> String {
+        match self {
+            RulePreset::LifeLike(rulestring) => rulestring.to_string(),
+            RulePreset::BriansBrain(refractory_states) => format!("BriansBrain({})", refractory_states),
+        }
+    }
+
+    pub fn build(&self) -> (CellRule, Vec<CellData>) {
+        match self {
+            RulePreset::LifeLike(rulestring) => {
+                let rule = RuleString::parse(rulestring)
+                    .expect("built-in rulestring failed to parse")
+                    .to_rule();
+                (rule, life_like_colors())
+            },
+            RulePreset::BriansBrain(refractory_states) => (
+                brians_brain_rule(*refractory_states),
+                brians_brain_colors(*refractory_states),
+            ),
+        }
+    }
 }
 
+/// A handful of well-known rules to cycle through at runtime.
+const RULE_PRESETS: [RulePreset; 4] = [
+    RulePreset::LifeLike("B3/S23"),
+    RulePreset::LifeLike("B36/S23"),
+    RulePreset::LifeLike("B2/S"),
+    RulePreset::BriansBrain(2),
+];
+
 fn get_x_y(i: usize) -> (usize, usize) {
     (i % CELL_WIDTH, i / CELL_HEIGHT)
 }
@@ -59,54 +285,350 @@ fn get_idx(x: usize, y: usize) -> usize {
     y * CELL_HEIGHT + x
 }
 
-fn cell_generation_tick(mut cells: CellGrid, rules: &mut CellRules) -> CellGrid {
-    for i in 0..cells.len() {
-        let (x, y) = get_x_y(i);
-        let mut live_count: u8 = 0;
-
-        for _x in -1..2 as isize {
-            for _y in -1..2 as isize {
-                if !(_x == 0 && _y == 0) {
-                    let x_m = _x + x as isize;
-                    let y_m = _y + y as isize;
-                    if x_m >= 0 && x_m < CELL_WIDTH as isize && y_m >= 0 && y_m < CELL_HEIGHT as isize {
-                        // within range
-                        let idx = get_idx(x_m as usize, y_m as usize);
-                        if cells[idx] { live_count += 1; }
-                    }
+/// Tracks which cell indices changed in the last generation, so the next
+/// tick only needs to recompute those cells and their neighbors instead of
+/// rescanning the whole grid. `None` forces a full rescan, which is needed
+/// for the very first tick and any time the grid was mutated out of band
+/// (painted, loaded, or a rule swap) so the active set no longer reflects
+/// what actually changed.
+struct TickCache {
+    active: Option<HashSet<usize>>,
+}
+
+impl TickCache {
+    pub fn new() -> Self {
+        TickCache { active: None }
+    }
+
+    pub fn invalidate(&mut self) {
+        self.active = None;
+    }
+}
+
+/// Selects what happens to neighbor counting at the edge of the grid.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BoundaryMode {
+    /// Out-of-range neighbors simply aren't counted (today's behavior).
+    Dead,
+    /// Out-of-range neighbors wrap around to the opposite edge, making the
+    /// grid a seamless torus.
+    Toroidal,
+}
+
+impl BoundaryMode {
+    pub fn toggle(self) -> Self {
+        match self {
+            BoundaryMode::Dead => BoundaryMode::Toroidal,
+            BoundaryMode::Toroidal => BoundaryMode::Dead,
+        }
+    }
+}
+
+/// Invokes `f` with the index of every neighbor of `(x, y)`, applying
+/// `mode`'s edge behavior.
+fn for_each_neighbor(x: usize, y: usize, mode: BoundaryMode, mut f: impl FnMut(usize)) {
+    for _x in -1..2_isize {
+        for _y in -1..2_isize {
+            if !(_x == 0 && _y == 0) {
+                let x_m = _x + x as isize;
+                let y_m = _y + y as isize;
+                match mode {
+                    BoundaryMode::Dead => {
+                        if x_m >= 0 && x_m < CELL_WIDTH as isize && y_m >= 0 && y_m < CELL_HEIGHT as isize {
+                            f(get_idx(x_m as usize, y_m as usize));
+                        }
+                    },
+                    BoundaryMode::Toroidal => {
+                        let wx = (x_m + CELL_WIDTH as isize) % CELL_WIDTH as isize;
+                        let wy = (y_m + CELL_HEIGHT as isize) % CELL_HEIGHT as isize;
+                        f(get_idx(wx as usize, wy as usize));
+                    },
                 }
             }
         }
+    }
+}
+
+#[cfg(test)]
+mod boundary_mode_tests {
+    use super::*;
 
-        let mut state = CellState {
-            x,
-            y,
-            alive: cells[i],
-            neighbors_alive: live_count,
-        };
+    fn neighbors(x: usize, y: usize, mode: BoundaryMode) -> HashSet<usize> {
+        let mut out = HashSet::new();
+        for_each_neighbor(x, y, mode, |idx| { out.insert(idx); });
+        out
+    }
 
-        for rule in rules.iter_rules() {
-            state.alive = rule(&cells, state.clone());
+    #[test]
+    fn toroidal_wraps_corner_neighbors_to_the_opposite_edges() {
+        let wrapped = neighbors(0, 0, BoundaryMode::Toroidal);
+        assert_eq!(wrapped.len(), 8, "a toroidal corner should still have 8 distinct neighbors");
+
+        let expected = [
+            (CELL_WIDTH - 1, CELL_HEIGHT - 1), (0, CELL_HEIGHT - 1), (1, CELL_HEIGHT - 1),
+            (CELL_WIDTH - 1, 0),                                     (1, 0),
+            (CELL_WIDTH - 1, 1),               (0, 1),               (1, 1),
+        ];
+        for (x, y) in expected {
+            assert!(wrapped.contains(&get_idx(x, y)), "missing wrapped neighbor ({}, {})", x, y);
         }
+    }
 
-        cells[i] = state.alive;
+    #[test]
+    fn dead_boundary_drops_out_of_range_corner_neighbors() {
+        let dead = neighbors(0, 0, BoundaryMode::Dead);
+        assert_eq!(dead.len(), 3, "a dead-boundary corner only has 3 in-range neighbors");
+        assert!(!dead.contains(&get_idx(CELL_WIDTH - 1, CELL_HEIGHT - 1)));
     }
-    cells
 }
 
-fn seed_cells(mut rng: ThreadRng) -> CellGrid {
-    let mut cells: CellGrid = [false; CELL_WIDTH * CELL_HEIGHT];
-    for i in 0..cells.len() {
+fn compute_next_state(cells: &CellGrid, rules: &mut CellRules, i: usize, mode: BoundaryMode) -> CellId {
+    let (x, y) = get_x_y(i);
+    let mut live_count: u8 = 0;
+
+    for_each_neighbor(x, y, mode, |idx| {
+        if cells[idx] == 1 { live_count += 1; }
+    });
+
+    let mut state = CellState {
+        state: cells[i],
+        firing_neighbors: live_count,
+    };
+
+    for rule in rules.iter_rules() {
+        state.state = rule(cells, state.clone());
+    }
+
+    state.state
+}
+
+/// The union of `active` and every neighbor of each of its indices (per
+/// `mode`'s edge behavior) — the set of cells whose next state could
+/// possibly differ from their current one this generation.
+fn candidate_set(active: &HashSet<usize>, mode: BoundaryMode) -> HashSet<usize> {
+    let mut candidates = HashSet::new();
+    for &i in active {
+        candidates.insert(i);
+        let (x, y) = get_x_y(i);
+        for_each_neighbor(x, y, mode, |idx| {
+            candidates.insert(idx);
+        });
+    }
+    candidates
+}
+
+/// Recomputes every cell in `cells` against the previous generation, for
+/// the first tick of a grid or to check an incremental tick's result.
+fn full_tick(cells: &CellGrid, rules: &mut CellRules, mode: BoundaryMode) -> (CellGrid, HashSet<usize>) {
+    let mut next = *cells;
+    let mut flipped = HashSet::new();
+    for (i, &cell) in cells.iter().enumerate() {
+        let next_state = compute_next_state(cells, rules, i, mode);
+        if next_state != cell {
+            flipped.insert(i);
+        }
+        next[i] = next_state;
+    }
+    (next, flipped)
+}
+
+/// Recomputes only `active`'s candidate set, copying every other cell
+/// unchanged from the previous generation.
+fn incremental_tick(cells: &CellGrid, rules: &mut CellRules, active: &HashSet<usize>, mode: BoundaryMode) -> (CellGrid, HashSet<usize>) {
+    let mut next = *cells;
+    let mut flipped = HashSet::new();
+    for i in candidate_set(active, mode) {
+        let next_state = compute_next_state(cells, rules, i, mode);
+        if next_state != cells[i] {
+            flipped.insert(i);
+        }
+        next[i] = next_state;
+    }
+    (next, flipped)
+}
+
+fn cell_generation_tick(cells: CellGrid, rules: &mut CellRules, cache: &mut TickCache, mode: BoundaryMode) -> CellGrid {
+    let (next, flipped) = match &cache.active {
+        Some(active) => incremental_tick(&cells, rules, active, mode),
+        None => full_tick(&cells, rules, mode),
+    };
+
+    // Debug-only cross-check: a full rescan should always agree with the
+    // incremental result, catching cache-invalidation bugs before release.
+    if cfg!(debug_assertions) && cache.active.is_some() {
+        let (full_next, _) = full_tick(&cells, rules, mode);
+        debug_assert_eq!(next, full_next, "incremental tick cache diverged from a full rescan");
+    }
+
+    cache.active = Some(flipped);
+    next
+}
+
+#[cfg(test)]
+mod tick_cache_tests {
+    use super::*;
+
+    fn empty_grid() -> CellGrid {
+        [0; CELL_WIDTH * CELL_HEIGHT]
+    }
+
+    fn life_rules() -> CellRules {
+        let mut rules = CellRules::new();
+        rules.add_rule(RuleString::parse("B3/S23").unwrap().to_rule());
+        rules
+    }
+
+    /// Runs a few generations of `incremental_tick` alongside a `full_tick`
+    /// rescan of the same cells, mirroring the debug-only cross-check in
+    /// `cell_generation_tick`, so the dirty-cell cache's divergence risk is
+    /// actually exercised by `cargo test` instead of only a `debug_assert_eq!`
+    /// no CI run ever drives.
+    fn assert_incremental_matches_full(seed: CellGrid, mode: BoundaryMode, generations: u32) {
+        let mut rules = life_rules();
+        let (mut cells, mut active) = full_tick(&seed, &mut rules, mode);
+        for _ in 0..generations {
+            let (incremental, next_active) = incremental_tick(&cells, &mut rules, &active, mode);
+            let (full, _) = full_tick(&cells, &mut rules, mode);
+            assert_eq!(incremental, full, "incremental tick diverged from a full rescan");
+            cells = incremental;
+            active = next_active;
+        }
+    }
+
+    #[test]
+    fn incremental_tick_matches_full_tick_for_a_glider() {
+        let mut seed = empty_grid();
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        for (dx, dy) in glider {
+            seed[get_idx(10 + dx, 10 + dy)] = 1;
+        }
+        assert_incremental_matches_full(seed, BoundaryMode::Dead, 4);
+    }
+
+    #[test]
+    fn incremental_tick_matches_full_tick_for_a_still_life() {
+        let mut seed = empty_grid();
+        let block = [(0, 0), (1, 0), (0, 1), (1, 1)];
+        for (dx, dy) in block {
+            seed[get_idx(10 + dx, 10 + dy)] = 1;
+        }
+        assert_incremental_matches_full(seed, BoundaryMode::Dead, 3);
+    }
+}
+
+fn seed_cells(seed: u64) -> CellGrid {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut cells: CellGrid = [0; CELL_WIDTH * CELL_HEIGHT];
+    for (i, cell) in cells.iter_mut().enumerate() {
         let (x, y) = get_x_y(i);
         if (CELL_WIDTH / 2).wrapping_sub(x) < SEED_BOUNDING_BOX
             && (CELL_HEIGHT / 2).wrapping_sub(y) < SEED_BOUNDING_BOX
         {
-            cells[i] = rng.gen_bool(SEED_SPAWN_RATE);
+            *cell = if rng.gen_bool(SEED_SPAWN_RATE) { 1 } else { 0 };
         }
     }
     cells
 }
 
+/// On-disk representation of a world: the seed and rulestring that produced
+/// it plus the grid itself, so a dump can be reloaded and resumed exactly.
+/// `CellGrid` is a fixed-size array too large for serde to derive on
+/// directly, so the cells are carried as a plain `Vec<CellId>` and the
+/// dimensions are validated against the compiled-in grid size on load.
+#[derive(Serialize, Deserialize)]
+struct SavedWorld {
+    pub seed: u64,
+    pub rulestring: String,
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<CellId>,
+}
+
+impl SavedWorld {
+    pub fn capture(grid: &CellGrid, seed: u64, rulestring: &str) -> Self {
+        SavedWorld {
+            seed,
+            rulestring: rulestring.to_string(),
+            width: CELL_WIDTH,
+            height: CELL_HEIGHT,
+            cells: grid.to_vec(),
+        }
+    }
+
+    pub fn into_grid(self) -> Result<CellGrid, String> {
+        if self.width != CELL_WIDTH || self.height != CELL_HEIGHT {
+            return Err(format!(
+                "saved world is {}x{}, but this build is compiled for {}x{}",
+                self.width, self.height, CELL_WIDTH, CELL_HEIGHT
+            ));
+        }
+        if self.cells.len() != CELL_WIDTH * CELL_HEIGHT {
+            return Err(format!(
+                "saved world has {} cells, expected {}",
+                self.cells.len(),
+                CELL_WIDTH * CELL_HEIGHT
+            ));
+        }
+        let mut grid: CellGrid = [0; CELL_WIDTH * CELL_HEIGHT];
+        grid.copy_from_slice(&self.cells);
+        Ok(grid)
+    }
+}
+
+fn save_world(path: &str, grid: &CellGrid, seed: u64, rulestring: &str) -> std::io::Result<()> {
+    let saved = SavedWorld::capture(grid, seed, rulestring);
+    let json = serde_json::to_string_pretty(&saved).expect("failed to serialize world");
+    std::fs::write(path, json)
+}
+
+fn load_world(path: &str) -> std::io::Result<SavedWorld> {
+    let data = std::fs::read_to_string(path)?;
+    serde_json::from_str(&data).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Converts a window-space cursor position to a grid cell, or `None` if the
+/// cursor is outside the grid (e.g. over window chrome).
+fn screen_to_grid(mx: f64, my: f64, pos_x_m: f64, pos_y_m: f64) -> Option<(usize, usize)> {
+    if mx < 0.0 || my < 0.0 {
+        return None;
+    }
+    let gx = (mx / pos_x_m) as usize;
+    let gy = (my / pos_y_m) as usize;
+    if gx >= CELL_WIDTH || gy >= CELL_HEIGHT {
+        return None;
+    }
+    Some((gx, gy))
+}
+
+/// Paints every cell on the line between `from` and `to` (inclusive) via
+/// Bresenham's algorithm, so a fast drag doesn't leave gaps between the
+/// cursor positions sampled on consecutive move events.
+fn paint_line(grid: &mut CellGrid, from: (usize, usize), to: (usize, usize), value: CellId) {
+    let (x0, y0) = (from.0 as isize, from.1 as isize);
+    let (x1, y1) = (to.0 as isize, to.1 as isize);
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let sx: isize = if x0 < x1 { 1 } else { -1 };
+    let sy: isize = if y0 < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    let (mut x, mut y) = (x0, y0);
+    loop {
+        grid[get_idx(x as usize, y as usize)] = value;
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
 fn get_next_skip_index(dir: isize, i: usize, max: usize) -> usize {
     let tv = dir + i as isize;
     if tv < 0 { 0 }
@@ -114,20 +636,8 @@ fn get_next_skip_index(dir: isize, i: usize, max: usize) -> usize {
     else { tv as usize }
 }
 
-fn conway_rules(_grid: &CellGrid, cell: CellState) -> bool {
-    // Conway rules.
-    if cell.alive && cell.neighbors_alive > 3 { false }
-    else if cell.alive && cell.neighbors_alive < 2 { false }
-    else if !cell.alive {
-        if cell.neighbors_alive == 3 { true }
-        else { cell.alive }
-    } else {
-        cell.alive
-    }
-}
-
 fn main() {
-    let rng = thread_rng();
+    let mut seed: u64 = thread_rng().gen();
     let mut window: PistonWindow = WindowSettings::new(
         "Cells",
         (
@@ -143,16 +653,21 @@ fn main() {
     let pos_y_m: f64 = win_size.height / CELL_HEIGHT as f64;
     let rect = rectangle::square(0.0, 0.0, pos_x_m * CELL_SCALE);
     let mut ft: f64 = 0.0;
-    let mut snapshots: Vec<[bool; CELL_HEIGHT * CELL_WIDTH]> = vec![];
+    let mut snapshots: Vec<CellGrid> = vec![];
+    let mut rule_index: usize = 0;
     let mut cell_rules = CellRules::new();
-
-    // ADD RULES HERE
-        cell_rules.add_rule(conway_rules);
-    //
+    let (rule, mut cell_data) = RULE_PRESETS[rule_index].build();
+    cell_rules.add_rule(rule);
 
     let mut should_play: bool = false;
     let mut skip_index: usize = 0;
-    snapshots.push(seed_cells(rng.clone()));
+    let mut cursor_pos: (f64, f64) = (0.0, 0.0);
+    let mut left_down = false;
+    let mut right_down = false;
+    let mut last_paint_cell: Option<(usize, usize)> = None;
+    let mut tick_cache = TickCache::new();
+    let mut boundary_mode = BoundaryMode::Dead;
+    snapshots.push(seed_cells(seed));
     while let Some(e) = window.next() {
         if let Some(args) = e.update_args() {
             ft += args.dt;
@@ -163,7 +678,7 @@ fn main() {
                 } else {
                     // update
                     {
-                        snapshots.push(cell_generation_tick(snapshots.last().copied().expect("NO SNAPSHOT"), &mut cell_rules));
+                        snapshots.push(cell_generation_tick(snapshots.last().copied().expect("NO SNAPSHOT"), &mut cell_rules, &mut tick_cache, boundary_mode));
                         if snapshots.len() > SNAPSHOT_LIMIT {
                             snapshots.remove(0);
                         }
@@ -177,18 +692,17 @@ fn main() {
                 Key::Space => {
                     should_play = !should_play;
                 },
-                Key::Right => {
-                    if !should_play {
-                        // Don't move through while playing
-                        skip_index = get_next_skip_index(1, skip_index, SNAPSHOT_LIMIT - 1);
-                        if skip_index >= snapshots.len() || skip_index == snapshots.len() - 1 {
-                            snapshots.push(cell_generation_tick(snapshots.last().copied().expect("NO SNAPSHOT"), &mut cell_rules));
-                            if snapshots.len() > SNAPSHOT_LIMIT {
-                                snapshots.remove(0);
-                            }
+                Key::Right if !should_play => {
+                    // Don't move through while playing
+                    skip_index = get_next_skip_index(1, skip_index, SNAPSHOT_LIMIT - 1);
+                    if skip_index >= snapshots.len() || skip_index == snapshots.len() - 1 {
+                        snapshots.push(cell_generation_tick(snapshots.last().copied().expect("NO SNAPSHOT"), &mut cell_rules, &mut tick_cache, boundary_mode));
+                        if snapshots.len() > SNAPSHOT_LIMIT {
+                            snapshots.remove(0);
                         }
                     }
                 },
+                Key::Right => {},
                 Key::Up => {
                     // skip to end
                     skip_index = snapshots.len() - 1;
@@ -197,27 +711,122 @@ fn main() {
                     // skip to start
                     skip_index = 0;
                 },
-                Key::Left => {
-                    if !should_play {
-                        // Don't move through while playing
-                        skip_index = get_next_skip_index(-1, skip_index, snapshots.len() - 1);
+                Key::Left if !should_play => {
+                    // Don't move through while playing
+                    skip_index = get_next_skip_index(-1, skip_index, snapshots.len() - 1);
+                },
+                Key::Left => {},
+                Key::R => {
+                    // Cycle to the next rule preset.
+                    rule_index = (rule_index + 1) % RULE_PRESETS.len();
+                    let (rule, data) = RULE_PRESETS[rule_index].build();
+                    cell_rules.clear();
+                    cell_rules.add_rule(rule);
+                    cell_data = data;
+                    tick_cache.invalidate();
+                },
+                Key::T => {
+                    // Toggle between dead and toroidal grid edges.
+                    boundary_mode = boundary_mode.toggle();
+                    tick_cache.invalidate();
+                },
+                Key::F5 => {
+                    // Dump the current frame to disk.
+                    let cells = snapshots.get(skip_index).expect("NO SNAPSHOT?");
+                    if let Err(e) = save_world(SAVE_FILE, cells, seed, &RULE_PRESETS[rule_index].label()) {
+                        eprintln!("Failed to save world: {}", e);
+                    }
+                },
+                Key::F9 => {
+                    // Load a previously saved frame as a new branch point.
+                    match load_world(SAVE_FILE) {
+                        Ok(saved) => {
+                            let saved_seed = saved.seed;
+                            let saved_rulestring = saved.rulestring.clone();
+                            match saved.into_grid() {
+                            Ok(grid) => {
+                                seed = saved_seed;
+                                if let Some(idx) = RULE_PRESETS.iter().position(|p| p.label() == saved_rulestring) {
+                                    rule_index = idx;
+                                    let (rule, data) = RULE_PRESETS[rule_index].build();
+                                    cell_rules.clear();
+                                    cell_rules.add_rule(rule);
+                                    cell_data = data;
+                                }
+                                snapshots.truncate(skip_index + 1);
+                                snapshots.push(grid);
+                                skip_index = snapshots.len() - 1;
+                                tick_cache.invalidate();
+                            },
+                            Err(e) => eprintln!("Failed to load world: {}", e),
+                            }
+                        },
+                        Err(e) => eprintln!("Failed to load world: {}", e),
                     }
                 },
                 _ => {}
             }
+        } else if let Some(pos) = e.mouse_cursor_args() {
+            cursor_pos = (pos[0], pos[1]);
+            if !should_play && (left_down || right_down) {
+                if let Some(grid_pos) = screen_to_grid(cursor_pos.0, cursor_pos.1, pos_x_m, pos_y_m) {
+                    let value: CellId = if left_down { 1 } else { 0 };
+                    if skip_index != snapshots.len() - 1 {
+                        snapshots.truncate(skip_index + 1);
+                    }
+                    let grid = snapshots.get_mut(skip_index).expect("NO SNAPSHOT?");
+                    match last_paint_cell {
+                        Some(from) => paint_line(grid, from, grid_pos, value),
+                        None => grid[get_idx(grid_pos.0, grid_pos.1)] = value,
+                    }
+                    last_paint_cell = Some(grid_pos);
+                    tick_cache.invalidate();
+                }
+            }
+        } else if let Some(Button::Mouse(btn)) = e.press_args() {
+            if !should_play {
+                match btn {
+                    MouseButton::Left => left_down = true,
+                    MouseButton::Right => right_down = true,
+                    _ => {},
+                }
+                if left_down || right_down {
+                    if let Some(grid_pos) = screen_to_grid(cursor_pos.0, cursor_pos.1, pos_x_m, pos_y_m) {
+                        let value: CellId = if left_down { 1 } else { 0 };
+                        if skip_index != snapshots.len() - 1 {
+                            snapshots.truncate(skip_index + 1);
+                        }
+                        let grid = snapshots.get_mut(skip_index).expect("NO SNAPSHOT?");
+                        grid[get_idx(grid_pos.0, grid_pos.1)] = value;
+                        last_paint_cell = Some(grid_pos);
+                        tick_cache.invalidate();
+                    }
+                }
+            }
+        } else if let Some(Button::Mouse(btn)) = e.release_args() {
+            match btn {
+                MouseButton::Left => left_down = false,
+                MouseButton::Right => right_down = false,
+                _ => {},
+            }
+            if !left_down && !right_down {
+                last_paint_cell = None;
+            }
         }
         window.draw_2d(&e, |_c, g, _d| {
             let cells = snapshots.get(skip_index).expect("NO SNAPSHOT?");
             clear(BG_COLOR, g);
-            for i in 0..cells.len() {
+            for (i, &cell) in cells.iter().enumerate() {
                 let (x, y) = get_x_y(i);
-                if cells[i] {
-                    rectangle(
-                        CELL_COLOR,
-                        rect,
-                        _c.transform.trans(x as f64 * pos_x_m, y as f64 * pos_y_m),
-                        g,
-                    );
+                if cell != 0 {
+                    if let Some(data) = cell_data.get(cell as usize) {
+                        rectangle(
+                            data.color,
+                            rect,
+                            _c.transform.trans(x as f64 * pos_x_m, y as f64 * pos_y_m),
+                            g,
+                        );
+                    }
                 }
             }
         });